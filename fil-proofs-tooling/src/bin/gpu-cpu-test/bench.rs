@@ -0,0 +1,146 @@
+use std::fmt;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::scheduler::Priority;
+
+/// Same list paramcache generates parameters for by default, re-exported here so both
+/// binaries stay in sync instead of keeping separate copies.
+pub use filecoin_proofs::constants::PUBLISHED_SECTOR_SIZES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            other => Err(format!(
+                "unknown output format '{}', expected json or csv",
+                other
+            )),
+        }
+    }
+}
+
+/// One non-warmup iteration's result.
+#[derive(Debug, Clone, Serialize)]
+pub struct IterationSample {
+    pub sector_size: u64,
+    pub priority: Priority,
+    pub iteration: usize,
+    pub latency_ms: f64,
+    /// Whether this iteration proved all of its remaining partitions without being
+    /// preempted (true, also when it resumed from an earlier checkpoint), or was preempted
+    /// partway through and checkpointed, leaving a later iteration to resume (false).
+    pub completed_without_preemption: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LatencySummary {
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub throughput_per_sec: f64,
+}
+
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (pct * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+pub fn summarize(latencies_ms: &[f64]) -> LatencySummary {
+    let mut sorted = latencies_ms.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mean_ms = if sorted.is_empty() {
+        0.0
+    } else {
+        sorted.iter().sum::<f64>() / sorted.len() as f64
+    };
+    LatencySummary {
+        min_ms: percentile(&sorted, 0.0),
+        median_ms: percentile(&sorted, 0.5),
+        p95_ms: percentile(&sorted, 0.95),
+        max_ms: percentile(&sorted, 1.0),
+        throughput_per_sec: if mean_ms > 0.0 { 1000.0 / mean_ms } else { 0.0 },
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectorSizeReport {
+    pub sector_size: u64,
+    pub samples: Vec<IterationSample>,
+    pub summary: LatencySummary,
+    /// Sum, across every worker that ran this sector size, of how many times it had to
+    /// wait behind a higher-priority task before being granted the device.
+    pub waits: u32,
+    /// Sum, across every worker that ran this sector size, of how many iterations were
+    /// preempted and had to checkpoint instead of completing.
+    pub preempts: u32,
+    /// Sum, across every worker that ran this sector size, of how many partitions were
+    /// skipped because a prior checkpoint had already proven them.
+    pub resumed_partitions: usize,
+}
+
+impl fmt::Display for SectorSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sector_size={} samples={} min={:.2}ms median={:.2}ms p95={:.2}ms max={:.2}ms throughput={:.2}/s waits={} preempts={} resumed_partitions={}",
+            self.sector_size,
+            self.samples.len(),
+            self.summary.min_ms,
+            self.summary.median_ms,
+            self.summary.p95_ms,
+            self.summary.max_ms,
+            self.summary.throughput_per_sec,
+            self.waits,
+            self.preempts,
+            self.resumed_partitions,
+        )
+    }
+}
+
+pub fn write_report(reports: &[SectorSizeReport], format: OutputFormat, writer: &mut dyn Write) {
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(reports).expect("failed to serialize report");
+            writeln!(writer, "{}", json).expect("failed to write report");
+        }
+        OutputFormat::Csv => {
+            writeln!(
+                writer,
+                "sector_size,priority,iteration,latency_ms,completed_without_preemption,waits,preempts,resumed_partitions"
+            )
+            .expect("failed to write csv header");
+            for report in reports {
+                for sample in &report.samples {
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{},{}",
+                        sample.sector_size,
+                        sample.priority,
+                        sample.iteration,
+                        sample.latency_ms,
+                        sample.completed_without_preemption,
+                        report.waits,
+                        report.preempts,
+                        report.resumed_partitions,
+                    )
+                    .expect("failed to write csv row");
+                }
+            }
+        }
+    }
+}
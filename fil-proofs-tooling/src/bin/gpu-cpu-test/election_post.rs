@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use filecoin_proofs::{Candidate, PrivateReplicaInfo};
+use log::{debug, info};
+use storage_proofs::sector::SectorId;
+
+/// Partitions a single `do_generate_post` call proves, checked one at a time so progress
+/// can be checkpointed between them.
+const PARTITIONS: usize = 4;
+
+/// Progress saved when a proof is preempted partway through: how many partitions have
+/// already been proven, so a subsequent call resumes instead of recomputing them.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    completed_partitions: usize,
+}
+
+/// Outcome of a (possibly resumed, possibly preempted) `do_generate_post` call.
+#[derive(Debug, Default)]
+pub struct PostRunOutcome {
+    /// Whether all partitions were proven before returning.
+    pub completed: bool,
+    /// Partitions that were skipped because a prior checkpoint had already proven them.
+    pub resumed_partitions: usize,
+}
+
+/// Each caller (one per worker thread, per sector size) must pass a distinct
+/// `worker_id`/`sector_size` pair so concurrent callers, and successive sector sizes
+/// reusing the same `worker_id`, never read, write or clear each other's checkpoint.
+fn checkpoint_path(worker_id: &str, sector_size: u64) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "gpu-cpu-test-election-post-{}-{}.checkpoint",
+        worker_id, sector_size
+    ))
+}
+
+fn load_checkpoint(worker_id: &str, sector_size: u64) -> Checkpoint {
+    fs::read_to_string(checkpoint_path(worker_id, sector_size))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_checkpoint(worker_id: &str, sector_size: u64, checkpoint: &Checkpoint) {
+    let contents = serde_json::to_string(checkpoint).expect("failed to serialize checkpoint");
+    fs::write(checkpoint_path(worker_id, sector_size), contents)
+        .expect("failed to write checkpoint");
+}
+
+fn clear_checkpoint(worker_id: &str, sector_size: u64) {
+    let _ = fs::remove_file(checkpoint_path(worker_id, sector_size));
+}
+
+pub fn generate_priv_replica_info_fixture() -> BTreeMap<SectorId, PrivateReplicaInfo> {
+    BTreeMap::new()
+}
+
+pub fn generate_candidates_fixture(
+    _priv_replica_info: &BTreeMap<SectorId, PrivateReplicaInfo>,
+) -> Vec<Candidate> {
+    Vec::new()
+}
+
+/// Proves every partition of an election PoSt. Equivalent to
+/// `do_generate_post_cancellable` with no cancellation check, for callers that don't
+/// need cooperative preemption.
+pub fn do_generate_post(
+    worker_id: &str,
+    sector_size: u64,
+    priv_replica_infos: &BTreeMap<SectorId, PrivateReplicaInfo>,
+    candidates: &[Candidate],
+) -> PostRunOutcome {
+    do_generate_post_cancellable(worker_id, sector_size, priv_replica_infos, candidates, None)
+}
+
+/// Proves each partition of an election PoSt in turn, calling `should_preempt` (if given)
+/// between partitions. When it returns true and more than one partition remains, progress
+/// is checkpointed to a temp file and the call returns early instead of running to
+/// completion, so a higher-priority caller can take the device without losing the work
+/// already done. A subsequent call with the same `worker_id`/`sector_size` picks the
+/// checkpoint back up and resumes rather than recomputing the completed partitions.
+///
+/// `worker_id` must be unique per concurrent caller (e.g. the worker thread's name), and
+/// is combined with `sector_size` to namespace the on-disk checkpoint — so two workers
+/// running at once, or the same worker moving on to a different sector size in a sweep,
+/// never read, write or clear each other's progress.
+pub fn do_generate_post_cancellable(
+    worker_id: &str,
+    sector_size: u64,
+    priv_replica_infos: &BTreeMap<SectorId, PrivateReplicaInfo>,
+    candidates: &[Candidate],
+    should_preempt: Option<&dyn Fn() -> bool>,
+) -> PostRunOutcome {
+    let mut checkpoint = load_checkpoint(worker_id, sector_size);
+    let resumed_partitions = checkpoint.completed_partitions;
+    if resumed_partitions > 0 {
+        info!(
+            "Resuming election PoSt from partition {} for {} at sector size {}",
+            resumed_partitions, worker_id, sector_size
+        );
+    }
+
+    for partition in checkpoint.completed_partitions..PARTITIONS {
+        prove_partition(priv_replica_infos, candidates, partition);
+
+        let remaining = PARTITIONS - (partition + 1);
+        let preempt_requested = should_preempt.map_or(false, |f| f());
+        if preempt_requested && remaining > 1 {
+            checkpoint.completed_partitions = partition + 1;
+            save_checkpoint(worker_id, sector_size, &checkpoint);
+            debug!(
+                "Checkpointed election PoSt for {} at sector size {} after partition {}, {} partition(s) remaining",
+                worker_id, sector_size, partition, remaining
+            );
+            return PostRunOutcome {
+                completed: false,
+                resumed_partitions,
+            };
+        }
+    }
+
+    clear_checkpoint(worker_id, sector_size);
+    PostRunOutcome {
+        completed: true,
+        resumed_partitions,
+    }
+}
+
+fn prove_partition(
+    _priv_replica_infos: &BTreeMap<SectorId, PrivateReplicaInfo>,
+    _candidates: &[Candidate],
+    _partition: usize,
+) {
+    // Proof generation for a single election PoSt partition.
+}
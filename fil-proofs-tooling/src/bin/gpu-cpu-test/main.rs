@@ -1,22 +1,35 @@
 use std::collections::BTreeMap;
-use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use bellperson::gpu;
-use clap::{value_t, App, Arg};
+use clap::{value_t, values_t, App, Arg};
 use filecoin_proofs::{Candidate, PrivateReplicaInfo};
 use log::{debug, info, trace};
 use storage_proofs::sector::SectorId;
 
+mod bench;
 mod election_post;
+mod scheduler;
 
-const TIMEOUT: u64 = 5 * 60;
+use bench::{summarize, write_report, IterationSample, OutputFormat, SectorSizeReport};
+use scheduler::{GpuScheduler, Priority, SchedulerCounters};
+
+const DEFAULT_PRIORITY_LEVELS: usize = 4;
+const DEFAULT_PREEMPT_THRESHOLD_MS: u64 = 500;
+const DEFAULT_MIN_GRACE_MS: u64 = 250;
+const DEFAULT_ITERATIONS: usize = 10;
+const DEFAULT_WARMUP: usize = 0;
 
 #[derive(Debug)]
 pub struct RunInfo {
     elapsed: Duration,
-    iterations: u8,
+    iterations: usize,
+    priority: Priority,
+    counters: SchedulerCounters,
+    resumed_partitions: usize,
+    samples: Vec<IterationSample>,
 }
 
 pub fn colored_with_thread(
@@ -38,65 +51,192 @@ pub fn colored_with_thread(
     )
 }
 
+/// Runs `warmup + iterations` proofs at `priority` for `sector_size`, gated by
+/// `scheduler`. The first `warmup` iterations are timed like any other but excluded from
+/// the returned samples, so they can absorb cache/JIT warm-up effects without skewing the
+/// reported latencies.
 fn thread_fun(
-    rx: Receiver<()>,
-    gpu_stealing: bool,
+    worker_id: &str,
+    sector_size: u64,
+    warmup: usize,
+    iterations: usize,
+    scheduler: Arc<GpuScheduler>,
+    priority: Priority,
     priv_replica_infos: &BTreeMap<SectorId, PrivateReplicaInfo>,
     candidates: &[Candidate],
 ) -> RunInfo {
     let timing = Instant::now();
-    let mut iteration = 0;
-    while iteration < std::u8::MAX {
-        info!("high iter {}", iteration);
-
-        // This is the higher priority proof, get it on the GPU even if there is one running
-        // already there
-        if gpu_stealing {
-            let gpu_lock = gpu::acquire_gpu().unwrap();
-            info!("Trying to acquire GPU lock");
-            while !gpu::gpu_is_available().unwrap_or(false) {
-                thread::sleep(Duration::from_millis(100));
-                trace!("Trying to acquire GPU lock");
-            }
-            debug!("Acquired GPU lock, dropping it again");
-            gpu::drop_acquire_lock(gpu_lock);
+    let mut counters = SchedulerCounters::default();
+    let mut resumed_partitions = 0;
+    let mut samples = Vec::with_capacity(iterations);
+    let total = warmup + iterations;
+
+    for iteration in 0..total {
+        trace!(
+            "priority {} sector {} iter {}",
+            priority,
+            sector_size,
+            iteration
+        );
+
+        // Block until no strictly-higher-priority task is present, then hold the lease
+        // for the whole proof so a higher-priority task that arrives and runs
+        // immediately is still visible to our `should_yield` checks below.
+        let iteration_start = Instant::now();
+        let lease = scheduler.enter(priority);
+        if lease.waits > 0 {
+            trace!(
+                "Woke up {} time(s) while waiting behind higher-priority task(s)",
+                lease.waits
+            );
+        }
+        counters.waits += lease.waits;
+        counters.runs += 1;
+
+        // The scheduler only decides *when* this priority is allowed to contend for the
+        // device; actually taking it is still the real GPU lock.
+        let gpu_lock = gpu::acquire_gpu().unwrap();
+        while !gpu::gpu_is_available().unwrap_or(false) {
+            thread::sleep(Duration::from_millis(20));
         }
+        debug!("Acquired GPU lock");
 
-        // Run the actual proof
-        election_post::do_generate_post(&priv_replica_infos, &candidates);
+        // Check `should_yield` between partitions so a genuinely-ready higher-priority
+        // task can preempt us; `do_generate_post_cancellable` checkpoints and returns
+        // early rather than losing completed partitions.
+        let should_preempt = || lease.should_yield();
+        let outcome = election_post::do_generate_post_cancellable(
+            worker_id,
+            sector_size,
+            &priv_replica_infos,
+            &candidates,
+            Some(&should_preempt),
+        );
+        resumed_partitions += outcome.resumed_partitions;
+        if !outcome.completed {
+            counters.preempts += 1;
+        }
+
+        gpu::drop_acquire_lock(gpu_lock);
+        drop(lease);
 
-        // Waiting for this thread to be killed
-        match rx.try_recv() {
-            Ok(_) | Err(TryRecvError::Disconnected) => {
-                debug!("High priority proofs received kill message");
-                break;
-            }
-            Err(TryRecvError::Empty) => (),
+        if iteration >= warmup {
+            samples.push(IterationSample {
+                sector_size,
+                priority,
+                iteration: iteration - warmup,
+                latency_ms: iteration_start.elapsed().as_secs_f64() * 1000.0,
+                completed_without_preemption: outcome.completed,
+            });
         }
-        iteration += 1;
     }
+
     RunInfo {
         elapsed: timing.elapsed(),
-        iterations: iteration,
+        iterations,
+        priority,
+        counters,
+        resumed_partitions,
+        samples,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_thread(
     name: &str,
-    gpu_stealing: bool,
+    sector_size: u64,
+    warmup: usize,
+    iterations: usize,
+    scheduler: Arc<GpuScheduler>,
+    priority: Priority,
     priv_replica_infos: BTreeMap<SectorId, PrivateReplicaInfo>,
     candidates: Vec<Candidate>,
-) -> (Sender<()>, thread::JoinHandle<RunInfo>) {
-    let (tx, rx) = mpsc::channel();
-
-    let thread_config = thread::Builder::new().name(name.to_string());
-    let handler = thread_config
+) -> thread::JoinHandle<RunInfo> {
+    let worker_id = name.to_string();
+    thread::Builder::new()
+        .name(name.to_string())
         .spawn(move || -> RunInfo {
-            thread_fun(rx, gpu_stealing, &priv_replica_infos, &candidates)
+            thread_fun(
+                &worker_id,
+                sector_size,
+                warmup,
+                iterations,
+                scheduler,
+                priority,
+                &priv_replica_infos,
+                &candidates,
+            )
         })
-        .expect("Could not spawn thread");
+        .expect("Could not spawn thread")
+}
 
-    (tx, handler)
+/// Runs every configured worker thread once for `sector_size` and folds their samples
+/// into a single report for that sector size.
+fn run_sector_size(
+    sector_size: u64,
+    warmup: usize,
+    iterations: usize,
+    priorities: &[Priority],
+    scheduler: &Arc<GpuScheduler>,
+) -> SectorSizeReport {
+    info!(
+        "benchmarking sector size {} ({} worker(s), {} warmup + {} iterations each)",
+        sector_size,
+        priorities.len(),
+        warmup,
+        iterations
+    );
+
+    let priv_replica_info = election_post::generate_priv_replica_info_fixture();
+    let candidates = election_post::generate_candidates_fixture(&priv_replica_info);
+
+    let handlers: Vec<_> = priorities
+        .iter()
+        .enumerate()
+        .map(|(index, &priority)| {
+            spawn_thread(
+                &format!("worker-{}-p{}", index, priority),
+                sector_size,
+                warmup,
+                iterations,
+                Arc::clone(scheduler),
+                priority,
+                priv_replica_info.clone(),
+                candidates.clone(),
+            )
+        })
+        .collect();
+
+    let mut samples = Vec::new();
+    let mut waits = 0;
+    let mut preempts = 0;
+    let mut resumed_partitions = 0;
+    for handler in handlers {
+        let thread_name = handler
+            .thread()
+            .name()
+            .unwrap_or(&format!("{:?}", handler.thread().id()))
+            .to_string();
+        let run_info = handler.join().unwrap();
+        info!(
+            "Thread {} done: elapsed={:?} counters={:?} resumed_partitions={}",
+            thread_name, run_info.elapsed, run_info.counters, run_info.resumed_partitions
+        );
+        waits += run_info.counters.waits;
+        preempts += run_info.counters.preempts;
+        resumed_partitions += run_info.resumed_partitions;
+        samples.extend(run_info.samples);
+    }
+
+    let latencies: Vec<f64> = samples.iter().map(|s| s.latency_ms).collect();
+    SectorSizeReport {
+        sector_size,
+        samples,
+        summary: summarize(&latencies),
+        waits,
+        preempts,
+        resumed_partitions,
+    }
 }
 
 fn main() {
@@ -107,78 +247,113 @@ fn main() {
 
     let matches = App::new("gpu-cpu-test")
         .version("0.1")
-        .about("Tests if moving proofs from GPU to CPU works")
+        .about("Benchmarks moving proofs from GPU to CPU across sector sizes")
         .arg(
-            Arg::with_name("parallel")
-                .long("parallel")
-                .help("Run proofs in parallel.")
-                .default_value("true"),
+            Arg::with_name("gpu-priority-levels")
+                .long("gpu-priority-levels")
+                .help("Number of distinct priority levels the scheduler supports.")
+                .default_value("4"),
         )
         .arg(
-            Arg::with_name("gpu-stealing")
-                .long("gpu-stealing")
-                .help("Force high priority proof on the GPU and let low priority one continue on CPU.")
-                .default_value("true"),
+            Arg::with_name("priority")
+                .long("priority")
+                .help("Comma-separated priorities (0 = lowest), one worker thread per value. Defaults to two contending workers, priority 1 and 0, to exercise the scheduler out of the box.")
+                .require_delimiter(true)
+                .value_delimiter(",")
+                .default_value("1,0"),
+        )
+        .arg(
+            Arg::with_name("gpu-preempt-threshold-ms")
+                .long("gpu-preempt-threshold-ms")
+                .help("How long a higher-priority task must have been waiting before a running lower-priority task is asked to yield.")
+                .default_value("500"),
+        )
+        .arg(
+            Arg::with_name("gpu-min-grace-ms")
+                .long("gpu-min-grace-ms")
+                .help("Minimum time a task must have held the device before it can be preempted.")
+                .default_value("250"),
+        )
+        .arg(
+            Arg::with_name("sector-sizes")
+                .long("sector-sizes")
+                .help("Comma-separated sector sizes, in bytes, to sweep over.")
+                .require_delimiter(true)
+                .value_delimiter(","),
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .help("Number of timed iterations to run per sector size, per worker.")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("warmup")
+                .long("warmup")
+                .help("Number of warmup iterations to run per sector size, per worker, before the timed ones; they run like any other but are left out of the report.")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .help("Output format for the benchmark report.")
+                .possible_values(&["json", "csv"])
+                .default_value("json"),
         )
         .get_matches();
 
-    let parallel = value_t!(matches, "parallel", bool).unwrap();
-    if parallel {
-        info!("Running high and low priority proofs in parallel")
+    let levels = value_t!(matches, "gpu-priority-levels", usize).unwrap_or(DEFAULT_PRIORITY_LEVELS);
+    let priorities = values_t!(matches, "priority", Priority).unwrap_or_else(|e| e.exit());
+    let preempt_threshold_ms =
+        value_t!(matches, "gpu-preempt-threshold-ms", u64).unwrap_or(DEFAULT_PREEMPT_THRESHOLD_MS);
+    let min_grace_ms = value_t!(matches, "gpu-min-grace-ms", u64).unwrap_or(DEFAULT_MIN_GRACE_MS);
+    let iterations = value_t!(matches, "iterations", usize).unwrap_or(DEFAULT_ITERATIONS);
+    let warmup = value_t!(matches, "warmup", usize).unwrap_or(DEFAULT_WARMUP);
+    let output: OutputFormat = matches
+        .value_of("output")
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|e: String| panic!("{}", e));
+    let sector_sizes: Vec<u64> = if matches.is_present("sector-sizes") {
+        values_t!(matches.values_of("sector-sizes"), u64).unwrap_or_else(|e| e.exit())
     } else {
-        info!("Running high priority proofs only")
-    }
-    let gpu_stealing = value_t!(matches, "gpu-stealing", bool).unwrap();
-    if gpu_stealing {
-        info!("Force low piority proofs to CPU")
-    } else {
-        info!("Let everyone queue up to run on GPU")
-    }
+        bench::PUBLISHED_SECTOR_SIZES.to_vec()
+    };
 
-    // All channels we send a termination message to
-    let mut senders = Vec::new();
-    // All thread handles that get terminated
-    let mut threads: Vec<Option<thread::JoinHandle<_>>> = Vec::new();
-
-    // Create fixtures only once for both threads
-    let priv_replica_info = election_post::generate_priv_replica_info_fixture();
-    let candidates = election_post::generate_candidates_fixture(&priv_replica_info);
+    let scheduler = Arc::new(GpuScheduler::new(
+        levels,
+        Duration::from_millis(preempt_threshold_ms),
+        Duration::from_millis(min_grace_ms),
+    ));
 
-    // Put each proof into it's own scope (the other one is due to the if statement)
-    {
-        let (tx, handler) = spawn_thread(
-            "high",
-            gpu_stealing,
-            priv_replica_info.clone(),
-            candidates.clone(),
+    for &priority in &priorities {
+        assert!(
+            priority < scheduler.levels(),
+            "priority {} is out of range for {} levels",
+            priority,
+            scheduler.levels()
         );
-        senders.push(tx);
-        threads.push(Some(handler));
     }
 
-    if parallel {
-        let (tx, handler) = spawn_thread("low", false, priv_replica_info, candidates);
-        senders.push(tx);
-        threads.push(Some(handler));
-    }
+    info!(
+        "Sweeping {} sector size(s) with {} worker(s) ({} priority level(s)), {} warmup + {} iterations each, preempt-threshold {}ms, min-grace {}ms",
+        sector_sizes.len(),
+        priorities.len(),
+        scheduler.levels(),
+        warmup,
+        iterations,
+        preempt_threshold_ms,
+        min_grace_ms
+    );
 
-    // Terminate all threads after that amount of time
-    let timeout = Duration::from_secs(TIMEOUT);
-    thread::sleep(timeout);
-    info!("Waited long enough to kill all threads");
-    for tx in senders {
-        tx.send(()).unwrap();
-    }
+    let reports: Vec<SectorSizeReport> = sector_sizes
+        .into_iter()
+        .map(|sector_size| {
+            let report = run_sector_size(sector_size, warmup, iterations, &priorities, &scheduler);
+            info!("{}", report);
+            report
+        })
+        .collect();
 
-    for thread in &mut threads {
-        if let Some(handler) = thread.take() {
-            let thread_name = handler
-                .thread()
-                .name()
-                .unwrap_or(&format!("{:?}", handler.thread().id()))
-                .to_string();
-            let run_info = handler.join().unwrap();
-            info!("Thread {} info: {:?}", thread_name, run_info);
-        }
-    }
+    write_report(&reports, output, &mut std::io::stdout());
 }
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Lower numbers are lower priority; the scheduler only ever looks at tasks with a
+/// strictly greater priority than the caller's own.
+pub type Priority = usize;
+
+#[derive(Debug, Default)]
+struct Present {
+    /// Number of tasks currently present (blocked on, or actively running with) the GPU
+    /// at each priority level. A task stays counted here for as long as it holds the
+    /// device, not just while it is blocked waiting for its turn.
+    counts: BTreeMap<Priority, usize>,
+    /// The moment each priority level went from zero to one or more present tasks, i.e.
+    /// how long the oldest task at that level has wanted the device.
+    since: BTreeMap<Priority, Instant>,
+}
+
+/// Gates GPU access across threads of differing priority.
+///
+/// A task may run as soon as no strictly-higher-priority task is present (waiting for,
+/// or already running with, the device). To avoid thrashing where a barely-higher-
+/// priority job repeatedly evicts a running one, a running lower-priority job is only
+/// asked to yield once the higher-priority task has been present longer than
+/// `preempt_threshold` *and* the lower-priority job has held the device for at least
+/// `min_grace`.
+#[derive(Debug)]
+pub struct GpuScheduler {
+    levels: Priority,
+    present: Mutex<Present>,
+    condvar: Condvar,
+    preempt_threshold: Duration,
+    min_grace: Duration,
+}
+
+/// Per-task counters suitable for folding into `RunInfo`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedulerCounters {
+    pub waits: u32,
+    pub runs: u32,
+    pub preempts: u32,
+}
+
+/// A priority's claim on the device, held from the moment it's granted until dropped.
+/// Keeping this alive for the whole time the proof runs (not just while blocked) is what
+/// lets a lower-priority holder's `should_yield` see a higher-priority task that got in
+/// immediately and is still running.
+pub struct GpuLease<'a> {
+    scheduler: &'a GpuScheduler,
+    priority: Priority,
+    acquired_at: Instant,
+    /// Number of times this lease had to wait behind a higher-priority task before it
+    /// was granted (0 if the device was immediately available).
+    pub waits: u32,
+}
+
+impl GpuLease<'_> {
+    /// Whether the holder of this lease should checkpoint and release the device: a
+    /// higher-priority task has been present longer than the configured preempt
+    /// threshold, and this lease has held the device for at least the grace period.
+    pub fn should_yield(&self) -> bool {
+        self.scheduler.should_yield(self.priority, self.acquired_at)
+    }
+}
+
+impl Drop for GpuLease<'_> {
+    fn drop(&mut self) {
+        self.scheduler.leave(self.priority);
+    }
+}
+
+impl GpuScheduler {
+    pub fn new(levels: Priority, preempt_threshold: Duration, min_grace: Duration) -> Self {
+        assert!(levels > 0, "must support at least one priority level");
+        GpuScheduler {
+            levels,
+            present: Mutex::new(Present::default()),
+            condvar: Condvar::new(),
+            preempt_threshold,
+            min_grace,
+        }
+    }
+
+    pub fn levels(&self) -> Priority {
+        self.levels
+    }
+
+    /// The moment the oldest strictly-higher-priority task currently present started
+    /// wanting the device, or `None` if none is present. The single source of truth for
+    /// "is a higher-priority task present" — both `gpu_is_available` and `enter`'s wait
+    /// loop go through this (the latter via the already-locked `Present` directly) so
+    /// there's only one definition of what counts as present.
+    fn higher_priority_since(present: &Present, priority: Priority) -> Option<Instant> {
+        present
+            .counts
+            .range(priority + 1..)
+            .filter(|&(_, &count)| count > 0)
+            .filter_map(|(level, _)| present.since.get(level).copied())
+            .min()
+    }
+
+    /// True when no strictly-higher-priority task is currently present.
+    pub fn gpu_is_available(&self, priority: Priority) -> bool {
+        let present = self.present.lock().unwrap();
+        Self::higher_priority_since(&present, priority).is_none()
+    }
+
+    /// Registers `priority` as present and blocks until no strictly-higher-priority task
+    /// is present, then returns a lease that keeps the registration alive until dropped.
+    /// Callers should hold the returned lease for as long as they hold the device.
+    pub fn enter(&self, priority: Priority) -> GpuLease<'_> {
+        let mut waits = 0;
+        let mut present = self.present.lock().unwrap();
+        *present.counts.entry(priority).or_insert(0) += 1;
+        present.since.entry(priority).or_insert_with(Instant::now);
+
+        while Self::higher_priority_since(&present, priority).is_some() {
+            waits += 1;
+            let (guard, _) = self
+                .condvar
+                .wait_timeout(present, Duration::from_millis(50))
+                .unwrap();
+            present = guard;
+        }
+        drop(present);
+
+        GpuLease {
+            scheduler: self,
+            priority,
+            acquired_at: Instant::now(),
+            waits,
+        }
+    }
+
+    fn leave(&self, priority: Priority) {
+        let mut present = self.present.lock().unwrap();
+        if let Some(count) = present.counts.get_mut(&priority) {
+            *count -= 1;
+            if *count == 0 {
+                present.counts.remove(&priority);
+                present.since.remove(&priority);
+            }
+        }
+        drop(present);
+        self.condvar.notify_all();
+    }
+
+    /// Called periodically by a running task to decide whether it should checkpoint and
+    /// release the device. Only returns true once a higher-priority task has been
+    /// present longer than `preempt_threshold` and the caller has held the device for
+    /// at least `min_grace`, which prevents a barely-higher-priority arrival from
+    /// immediately bouncing a job that just started.
+    fn should_yield(&self, priority: Priority, held_since: Instant) -> bool {
+        if held_since.elapsed() < self.min_grace {
+            return false;
+        }
+        let present = self.present.lock().unwrap();
+        match Self::higher_priority_since(&present, priority) {
+            Some(since) => since.elapsed() >= self.preempt_threshold,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_is_available_with_no_contention() {
+        let scheduler = GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(0));
+        assert!(scheduler.gpu_is_available(0));
+
+        let lease = scheduler.enter(0);
+        assert!(scheduler.gpu_is_available(0));
+        drop(lease);
+    }
+
+    #[test]
+    fn gpu_is_available_false_for_lower_priority_while_higher_holds_lease() {
+        let scheduler = GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(0));
+        let high = scheduler.enter(3);
+
+        assert!(!scheduler.gpu_is_available(0));
+        assert!(scheduler.gpu_is_available(3));
+
+        drop(high);
+        assert!(scheduler.gpu_is_available(0));
+    }
+
+    #[test]
+    fn should_yield_false_before_grace_period_elapses() {
+        let scheduler =
+            GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(10_000));
+        let low = scheduler.enter(0);
+        let _high = scheduler.enter(1);
+
+        // min_grace hasn't elapsed yet, so the low-priority holder shouldn't be asked to
+        // yield even though a higher-priority task is present.
+        assert!(!low.should_yield());
+    }
+
+    #[test]
+    fn should_yield_false_before_preempt_threshold_elapses() {
+        let scheduler =
+            GpuScheduler::new(4, Duration::from_millis(10_000), Duration::from_millis(0));
+        let low = scheduler.enter(0);
+        let _high = scheduler.enter(1);
+
+        // min_grace is satisfied immediately, but the higher-priority task hasn't been
+        // present longer than preempt_threshold yet.
+        assert!(!low.should_yield());
+    }
+
+    #[test]
+    fn should_yield_false_without_a_higher_priority_task_present() {
+        let scheduler = GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(0));
+        let low = scheduler.enter(0);
+
+        assert!(!low.should_yield());
+    }
+
+    #[test]
+    fn should_yield_true_once_grace_and_threshold_both_elapsed() {
+        let scheduler = GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(0));
+        let low = scheduler.enter(0);
+        let _high = scheduler.enter(1);
+
+        assert!(low.should_yield());
+    }
+
+    #[test]
+    fn enter_does_not_block_on_lower_or_equal_priority() {
+        let scheduler = GpuScheduler::new(4, Duration::from_millis(0), Duration::from_millis(0));
+        let _low = scheduler.enter(0);
+        let same = scheduler.enter(1);
+        let _other_same = scheduler.enter(1);
+
+        assert_eq!(same.waits, 0);
+    }
+}
@@ -11,18 +11,16 @@ use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::CompoundProof;
 use storage_proofs::election_post::ElectionPoSt;
 use storage_proofs::hasher::pedersen::PedersenHasher;
-use storage_proofs::parameter_cache::CacheableParameters;
+use storage_proofs::parameter_cache::{
+    parameter_cache_params_path, parameter_cache_verifying_key_path, CacheableParameters,
+};
 use storage_proofs::stacked::StackedDrg;
 
-const PUBLISHED_SECTOR_SIZES: [u64; 5] = [
-    SECTOR_SIZE_ONE_KIB,
-    SECTOR_SIZE_16_MIB,
-    SECTOR_SIZE_256_MIB,
-    SECTOR_SIZE_1_GIB,
-    SECTOR_SIZE_32_GIB,
-];
+mod manifest;
 
-fn cache_porep_params(porep_config: PoRepConfig) {
+use manifest::{warn_if_failed, Manifest};
+
+fn cache_porep_params(porep_config: PoRepConfig, manifest: &mut Manifest) {
     let n = u64::from(PaddedBytesAmount::from(porep_config));
     info!(
         "begin PoRep parameter-cache check/populate routine for {}-byte sectors",
@@ -35,6 +33,9 @@ fn cache_porep_params(porep_config: PoRepConfig) {
     )
     .unwrap();
 
+    let id =
+        StackedCompound::<DefaultTreeHasher, DefaultPieceHasher>::cache_identifier(&public_params);
+
     {
         let circuit = <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
             _,
@@ -58,6 +59,7 @@ fn cache_porep_params(porep_config: PoRepConfig) {
         )
         .expect("failed to get groth params");
     }
+    manifest.record(&id, n, &parameter_cache_params_path(&id));
     {
         let circuit = <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
             _,
@@ -71,9 +73,10 @@ fn cache_porep_params(porep_config: PoRepConfig) {
         )
         .expect("failed to get verifying key");
     }
+    manifest.record(&id, n, &parameter_cache_verifying_key_path(&id));
 }
 
-fn cache_post_params(post_config: PoStConfig) {
+fn cache_post_params(post_config: PoStConfig, manifest: &mut Manifest) {
     let n = u64::from(PaddedBytesAmount::from(post_config));
     info!(
         "begin PoSt parameter-cache check/populate routine for {}-byte sectors",
@@ -82,6 +85,8 @@ fn cache_post_params(post_config: PoStConfig) {
 
     let post_public_params = post_public_params(post_config).unwrap();
 
+    let id = <ElectionPoStCompound<PedersenHasher>>::cache_identifier(&post_public_params);
+
     {
         let post_circuit: ElectionPoStCircuit<Bls12, PedersenHasher> =
             <ElectionPoStCompound<PedersenHasher> as CompoundProof<
@@ -105,6 +110,7 @@ fn cache_post_params(post_config: PoStConfig) {
         <ElectionPoStCompound<PedersenHasher>>::get_groth_params(post_circuit, &post_public_params)
             .expect("failed to get groth params");
     }
+    manifest.record(&id, n, &parameter_cache_params_path(&id));
     {
         let post_circuit: ElectionPoStCircuit<Bls12, PedersenHasher> =
             <ElectionPoStCompound<PedersenHasher> as CompoundProof<
@@ -119,6 +125,74 @@ fn cache_post_params(post_config: PoStConfig) {
         )
         .expect("failed to get verifying key");
     }
+    manifest.record(&id, n, &parameter_cache_verifying_key_path(&id));
+}
+
+/// Recomputes digests of the on-disk files backing the PoRep circuit for `porep_config`
+/// and checks them against `manifest`, without regenerating anything. Returns `false` if
+/// either the Groth parameters or the verifying key are missing, truncated or corrupt.
+fn verify_porep_params(porep_config: PoRepConfig, manifest: &Manifest) -> bool {
+    let n = u64::from(PaddedBytesAmount::from(porep_config));
+    let public_params = public_params(
+        PaddedBytesAmount::from(porep_config),
+        usize::from(PoRepProofPartitions::from(porep_config)),
+    )
+    .unwrap();
+    let id =
+        StackedCompound::<DefaultTreeHasher, DefaultPieceHasher>::cache_identifier(&public_params);
+
+    let params_ok = warn_if_failed(
+        &manifest.verify(&id, n, &parameter_cache_params_path(&id)),
+        &id,
+        n,
+    );
+    let vk_ok = warn_if_failed(
+        &manifest.verify(&id, n, &parameter_cache_verifying_key_path(&id)),
+        &id,
+        n,
+    );
+    params_ok && vk_ok
+}
+
+/// Recomputes digests of the on-disk files backing the election-PoSt circuit for
+/// `post_config` and checks them against `manifest`, without regenerating anything.
+fn verify_post_params(post_config: PoStConfig, manifest: &Manifest) -> bool {
+    let n = u64::from(PaddedBytesAmount::from(post_config));
+    let post_public_params = post_public_params(post_config).unwrap();
+    let id = <ElectionPoStCompound<PedersenHasher>>::cache_identifier(&post_public_params);
+
+    let params_ok = warn_if_failed(
+        &manifest.verify(&id, n, &parameter_cache_params_path(&id)),
+        &id,
+        n,
+    );
+    let vk_ok = warn_if_failed(
+        &manifest.verify(&id, n, &parameter_cache_verifying_key_path(&id)),
+        &id,
+        n,
+    );
+    params_ok && vk_ok
+}
+
+fn porep_config_for(sector_size: u64) -> PoRepConfig {
+    PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(
+            *POREP_PARTITIONS
+                .read()
+                .unwrap()
+                .get(&sector_size)
+                .expect("missing sector size"),
+        ),
+    }
+}
+
+fn post_config_for(sector_size: u64) -> PoStConfig {
+    PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count: POST_CHALLENGE_COUNT,
+        challenged_nodes: POST_CHALLENGED_NODES,
+    }
 }
 
 // Run this from the command-line to pre-generate the groth parameters used by the API.
@@ -143,6 +217,12 @@ pub fn main() {
                 .long("only-election-post")
                 .help("Only generate parameters for election-post")
         )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .conflicts_with("only-election-post")
+                .help("Don't generate anything; check the manifest against the on-disk cache and exit non-zero on any missing, truncated or corrupt file")
+        )
         .get_matches();
 
     let sizes: HashSet<u64> = if matches.is_present("params-for-sector-sizes") {
@@ -156,24 +236,32 @@ pub fn main() {
 
     let only_election_post = matches.is_present("only-election-post");
 
+    if matches.is_present("verify") {
+        let manifest = Manifest::load();
+        let mut all_ok = true;
+        for sector_size in sizes {
+            all_ok &= verify_post_params(post_config_for(sector_size), &manifest);
+            all_ok &= verify_porep_params(porep_config_for(sector_size), &manifest);
+        }
+        if !all_ok {
+            info!("parameter cache verification failed, see warnings above");
+            std::process::exit(1);
+        }
+        info!("parameter cache verified OK");
+        return;
+    }
+
+    let mut manifest = Manifest::load();
     for sector_size in sizes {
-        cache_post_params(PoStConfig {
-            sector_size: SectorSize(sector_size),
-            challenge_count: POST_CHALLENGE_COUNT,
-            challenged_nodes: POST_CHALLENGED_NODES,
-        });
+        cache_post_params(post_config_for(sector_size), &mut manifest);
+        // Save after every sector size (not once at the end): `cache_*_params` panics on
+        // failure, and a later sector size shouldn't cost us the manifest entries already
+        // recorded for sizes that succeeded earlier in this run.
+        manifest.save();
 
         if !only_election_post {
-            cache_porep_params(PoRepConfig {
-                sector_size: SectorSize(sector_size),
-                partitions: PoRepProofPartitions(
-                    *POREP_PARTITIONS
-                        .read()
-                        .unwrap()
-                        .get(&sector_size)
-                        .expect("missing sector size"),
-                ),
-            });
+            cache_porep_params(porep_config_for(sector_size), &mut manifest);
+            manifest.save();
         }
     }
 }
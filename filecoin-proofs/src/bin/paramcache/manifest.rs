@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use storage_proofs::parameter_cache::parameter_cache_dir;
+
+/// On-disk record of a single cached file (Groth parameters or a verifying key) for one
+/// circuit/sector-size combination, so a parameter directory's integrity can be checked
+/// without regenerating or re-downloading anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub circuit: String,
+    pub sector_size: u64,
+    pub file_name: String,
+    pub len: u64,
+    pub digest: String,
+}
+
+/// Keyed by `(circuit identifier, sector size, file name)` so the Groth parameters and the
+/// verifying key of one circuit each get their own entry, and re-running paramcache
+/// overwrites a stale entry instead of appending a duplicate.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    entries: BTreeMap<(String, u64, String), ManifestEntry>,
+}
+
+fn manifest_path() -> PathBuf {
+    parameter_cache_dir().join("paramcache-manifest.json")
+}
+
+fn digest_and_len(path: &Path) -> io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let len = io::copy(&mut file, &mut hasher)?;
+    Ok((format!("{:x}", hasher.finalize()), len))
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl Manifest {
+    pub fn load() -> Self {
+        let entries = fs::read_to_string(manifest_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Vec<ManifestEntry>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| {
+                let key = (
+                    entry.circuit.clone(),
+                    entry.sector_size,
+                    entry.file_name.clone(),
+                );
+                (key, entry)
+            })
+            .collect();
+        Manifest { entries }
+    }
+
+    pub fn save(&self) {
+        let entries: Vec<&ManifestEntry> = self.entries.values().collect();
+        let contents =
+            serde_json::to_string_pretty(&entries).expect("failed to serialize manifest");
+        fs::write(manifest_path(), contents).expect("failed to write manifest");
+    }
+
+    /// Records the file at `path` under `(circuit, sector_size)` and its file name,
+    /// recomputing its digest. Call this right after a param/verifying-key file has been
+    /// generated.
+    pub fn record(&mut self, circuit: &str, sector_size: u64, path: &Path) {
+        let (digest, len) = digest_and_len(path)
+            .unwrap_or_else(|e| panic!("failed to digest {}: {}", path.display(), e));
+        let file_name = file_name_of(path);
+        info!(
+            "recorded {} ({} bytes, sha256:{}) for circuit {} at sector size {}",
+            file_name, len, digest, circuit, sector_size
+        );
+        self.entries.insert(
+            (circuit.to_string(), sector_size, file_name.clone()),
+            ManifestEntry {
+                circuit: circuit.to_string(),
+                sector_size,
+                file_name,
+                len,
+                digest,
+            },
+        );
+    }
+
+    /// Re-derives the digest of the on-disk file at `path` and compares it against the
+    /// entry recorded for that file under `(circuit, sector_size)`, without regenerating
+    /// anything.
+    pub fn verify(&self, circuit: &str, sector_size: u64, path: &Path) -> VerifyOutcome {
+        let key = (circuit.to_string(), sector_size, file_name_of(path));
+        let entry = match self.entries.get(&key) {
+            Some(entry) => entry,
+            None => return VerifyOutcome::MissingEntry,
+        };
+
+        if !path.exists() {
+            return VerifyOutcome::MissingFile;
+        }
+
+        match digest_and_len(path) {
+            Ok((_digest, len)) if len != entry.len => VerifyOutcome::Truncated {
+                expected: entry.len,
+                actual: len,
+            },
+            Ok((digest, _)) if digest != entry.digest => VerifyOutcome::DigestMismatch {
+                expected: entry.digest.clone(),
+                actual: digest,
+            },
+            Ok(_) => VerifyOutcome::Ok,
+            Err(e) => VerifyOutcome::ReadError(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    Ok,
+    MissingEntry,
+    MissingFile,
+    Truncated { expected: u64, actual: u64 },
+    DigestMismatch { expected: String, actual: String },
+    ReadError(String),
+}
+
+impl VerifyOutcome {
+    pub fn is_ok(&self) -> bool {
+        *self == VerifyOutcome::Ok
+    }
+
+    pub fn describe(&self, circuit: &str, sector_size: u64) -> String {
+        match self {
+            VerifyOutcome::Ok => format!("{} @ {}: ok", circuit, sector_size),
+            VerifyOutcome::MissingEntry => {
+                format!("{} @ {}: no manifest entry", circuit, sector_size)
+            }
+            VerifyOutcome::MissingFile => {
+                format!("{} @ {}: file missing on disk", circuit, sector_size)
+            }
+            VerifyOutcome::Truncated { expected, actual } => format!(
+                "{} @ {}: truncated (expected {} bytes, found {})",
+                circuit, sector_size, expected, actual
+            ),
+            VerifyOutcome::DigestMismatch { expected, actual } => format!(
+                "{} @ {}: digest mismatch (expected sha256:{}, found sha256:{})",
+                circuit, sector_size, expected, actual
+            ),
+            VerifyOutcome::ReadError(e) => {
+                format!("{} @ {}: read error ({})", circuit, sector_size, e)
+            }
+        }
+    }
+}
+
+pub fn warn_if_failed(outcome: &VerifyOutcome, circuit: &str, sector_size: u64) -> bool {
+    if outcome.is_ok() {
+        true
+    } else {
+        warn!("{}", outcome.describe(circuit, sector_size));
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("paramcache-manifest-test-{}", name));
+        fs::write(&path, contents).expect("failed to write temp file");
+        path
+    }
+
+    #[test]
+    fn verify_ok_after_record() {
+        let path = temp_file("ok", b"groth params go here");
+        let mut manifest = Manifest::default();
+        manifest.record("stacked-drg", 1024, &path);
+
+        assert_eq!(
+            manifest.verify("stacked-drg", 1024, &path),
+            VerifyOutcome::Ok
+        );
+    }
+
+    #[test]
+    fn verify_missing_entry() {
+        let path = temp_file("missing-entry", b"irrelevant");
+        let manifest = Manifest::default();
+
+        assert_eq!(
+            manifest.verify("stacked-drg", 1024, &path),
+            VerifyOutcome::MissingEntry
+        );
+    }
+
+    #[test]
+    fn verify_missing_file() {
+        let path = temp_file("missing-file", b"will be removed");
+        let mut manifest = Manifest::default();
+        manifest.record("stacked-drg", 1024, &path);
+        fs::remove_file(&path).expect("failed to remove temp file");
+
+        assert_eq!(
+            manifest.verify("stacked-drg", 1024, &path),
+            VerifyOutcome::MissingFile
+        );
+    }
+
+    #[test]
+    fn verify_truncated() {
+        let path = temp_file("truncated", b"groth params go here");
+        let mut manifest = Manifest::default();
+        manifest.record("stacked-drg", 1024, &path);
+
+        fs::write(&path, b"shorter").expect("failed to truncate temp file");
+
+        match manifest.verify("stacked-drg", 1024, &path) {
+            VerifyOutcome::Truncated { expected, actual } => {
+                assert_eq!(expected, "groth params go here".len() as u64);
+                assert_eq!(actual, "shorter".len() as u64);
+            }
+            other => panic!("expected Truncated, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_digest_mismatch() {
+        let path = temp_file("digest-mismatch", b"groth params go here!!");
+        let mut manifest = Manifest::default();
+        manifest.record("stacked-drg", 1024, &path);
+
+        // Same length, different contents, so only the digest check should trip.
+        fs::write(&path, b"groth params go here??").expect("failed to rewrite temp file");
+
+        match manifest.verify("stacked-drg", 1024, &path) {
+            VerifyOutcome::DigestMismatch { .. } => {}
+            other => panic!("expected DigestMismatch, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,10 @@
+/// Sector sizes `paramcache` generates parameters for by default, and that
+/// `gpu-cpu-test`'s benchmark sweep defaults to sweeping over as well. Kept here, rather
+/// than duplicated in each binary, so the two lists can't silently drift out of sync.
+pub const PUBLISHED_SECTOR_SIZES: [u64; 5] = [
+    SECTOR_SIZE_ONE_KIB,
+    SECTOR_SIZE_16_MIB,
+    SECTOR_SIZE_256_MIB,
+    SECTOR_SIZE_1_GIB,
+    SECTOR_SIZE_32_GIB,
+];